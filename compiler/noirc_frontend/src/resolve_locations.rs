@@ -1,10 +1,131 @@
+use std::collections::{HashMap, HashSet};
+
 use arena::Index;
-use noirc_errors::Location;
+use fm::FileId;
+use noirc_errors::{Location, Span};
 
 use crate::hir_def::expr::HirExpression;
 use crate::hir_def::types::Type;
 
-use crate::node_interner::{DefinitionKind, Node, NodeInterner};
+use crate::node_interner::{
+    DefinitionId, DefinitionKind, FuncId, GlobalId, Node, NodeInterner, StructId, TraitId,
+};
+
+/// The resolved type and definition kind of the item under the cursor,
+/// returned by [NodeInterner::describe_location] for LSP hover tooltips.
+#[derive(Debug, Clone)]
+pub struct HoverInfo {
+    pub typ: Type,
+    pub kind: HoverKind,
+}
+
+/// What kind of thing [HoverInfo] describes.
+#[derive(Debug, Clone)]
+pub enum HoverKind {
+    /// Format the full `fn name(..) -> ..` signature via
+    /// [NodeInterner::function_signature].
+    Function(FuncId),
+    Local,
+    Global(GlobalId),
+    StructField,
+    /// An indirect call through a function value, where there's no single
+    /// `FuncId` the callee resolves to.
+    Call,
+}
+
+/// Identifies the specific symbol a reference site points at, mirroring the
+/// match arms in [NodeInterner::resolve_expression_location]. Compared for
+/// equality by [NodeInterner::references_to], the scan that backs
+/// [NodeInterner::find_all_references].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReferenceId {
+    Definition(DefinitionId),
+    StructField(StructId, String),
+    TraitMethod(TraitId, String),
+}
+
+/// The sorted intervals for a single file, built fresh from
+/// [NodeInterner::id_to_location] on every [NodeInterner::find_location_index]
+/// call so it can never serve a stale result after an edit (there's no
+/// incrementally-maintained cache to invalidate). Generic over the value
+/// stored per interval so the containment/selection logic can be unit tested
+/// without needing to construct an [Index].
+///
+/// Each file's intervals are kept sorted by span start alongside a running
+/// maximum end, so a query can binary-search for the candidates that could
+/// possibly contain the query and prune the rest via the running maximum
+/// before checking containment.
+struct FileIntervalIndex<V> {
+    /// Sorted by `span.start()`.
+    intervals: Vec<(Span, V)>,
+    /// `max_end[i]` is the maximum `span.end()` over `intervals[..=i]`, so a
+    /// query can stop walking backwards as soon as it sees an entry whose
+    /// `max_end` can't reach the query's end.
+    max_end: Vec<u32>,
+    dirty: bool,
+}
+
+impl<V> Default for FileIntervalIndex<V> {
+    fn default() -> Self {
+        Self { intervals: Vec::new(), max_end: Vec::new(), dirty: false }
+    }
+}
+
+impl<V: Copy> FileIntervalIndex<V> {
+    fn insert(&mut self, span: Span, value: V) {
+        let position = self.intervals.partition_point(|(s, _)| s.start() <= span.start());
+        self.intervals.insert(position, (span, value));
+        self.dirty = true;
+    }
+
+    fn rebuild_max_end(&mut self) {
+        let mut running_max = 0;
+        self.max_end = self
+            .intervals
+            .iter()
+            .map(|(span, _)| {
+                running_max = running_max.max(span.end());
+                running_max
+            })
+            .collect();
+        self.dirty = false;
+    }
+
+    /// Returns the value of the smallest interval that fully contains
+    /// `query` (i.e. `interval.start() <= query.start() && interval.end() >=
+    /// query.end()`).
+    fn find_smallest_containing(&mut self, query: Span) -> Option<V> {
+        if self.dirty {
+            self.rebuild_max_end();
+        }
+
+        let query_start = query.start();
+        let query_end = query.end();
+        // Every interval that could possibly contain `query` starts at or before it.
+        let candidates_end = self.intervals.partition_point(|(s, _)| s.start() <= query_start);
+
+        let mut best: Option<(Span, V)> = None;
+        for i in (0..candidates_end).rev() {
+            if self.max_end[i] < query_end {
+                // No interval at or before `i` can reach `query`'s end either.
+                break;
+            }
+
+            let (candidate_span, candidate_value) = self.intervals[i];
+            if candidate_span.end() < query_end {
+                continue;
+            }
+
+            let is_smaller =
+                best.map_or(true, |(best_span, _)| candidate_span.is_smaller(&best_span));
+            if is_smaller {
+                best = Some((candidate_span, candidate_value));
+            }
+        }
+
+        best.map(|(_, value)| value)
+    }
+}
 
 impl NodeInterner {
     /// Scans the interner for the item which is located at that [Location]
@@ -12,23 +133,20 @@ impl NodeInterner {
     /// The [Location] may not necessarily point to the beginning of the item
     /// so we check if the location's span is contained within the start or end
     /// of each items [Span]
+    ///
+    /// Each call walks every entry of [NodeInterner::id_to_location] (in every
+    /// file) to build a fresh [FileIntervalIndex] for `location.file`, then
+    /// queries that index. The index is not kept between calls, so every call
+    /// costs at least one pass over all interned locations; only the query
+    /// step itself uses the sorted intervals.
     pub fn find_location_index(&self, location: Location) -> Option<impl Into<Index>> {
-        let mut location_candidate: Option<(&Index, &Location)> = None;
-
-        // Note: we can modify this in the future to not do a linear
-        // scan by storing a separate map of the spans or by sorting the locations.
+        let mut file_index = FileIntervalIndex::default();
         for (index, interned_location) in self.id_to_location.iter() {
-            if interned_location.contains(&location) {
-                if let Some(current_location) = location_candidate {
-                    if interned_location.span.is_smaller(&current_location.1.span) {
-                        location_candidate = Some((index, interned_location));
-                    }
-                } else {
-                    location_candidate = Some((index, interned_location));
-                }
+            if interned_location.file == location.file {
+                file_index.insert(interned_location.span, *index);
             }
         }
-        location_candidate.map(|(index, _location)| *index)
+        file_index.find_smallest_containing(location.span)
     }
 
     /// Returns the [Location] of the definition of the given Ident found at [Span] of the given [FileId].
@@ -50,6 +168,224 @@ impl NodeInterner {
         })
     }
 
+    /// Returns the resolved type and definition kind of the item under
+    /// `location`, for LSP hover tooltips.
+    ///
+    /// This follows the same node lookup as [NodeInterner::resolve_location]
+    /// and covers the `Ident`, `MemberAccess` and `Call` arms of
+    /// [NodeInterner::resolve_expression_location], yielding a [HoverInfo]
+    /// instead of a definition [Location]. Other expressions return `None`.
+    pub fn describe_location(&self, location: Location) -> Option<HoverInfo> {
+        let index = self.find_location_index(location)?;
+        self.describe_node(index)
+    }
+
+    /// Mirrors [NodeInterner::resolve_location]'s node dispatch, yielding a
+    /// [HoverInfo] instead of a definition [Location].
+    fn describe_node(&self, index: impl Into<Index>) -> Option<HoverInfo> {
+        let node = self.nodes.get(index.into())?;
+
+        match node {
+            Node::Function(func) => self.describe_node(func.as_expr()),
+            Node::Expression(expression) => self.describe_expression(expression),
+            _ => None,
+        }
+    }
+
+    /// Mirrors the `Ident`, `MemberAccess` and `Call` arms of
+    /// [NodeInterner::resolve_expression_location], yielding a [HoverInfo]
+    /// instead of a definition [Location]. Other expressions return `None`.
+    fn describe_expression(&self, expression: &HirExpression) -> Option<HoverInfo> {
+        match expression {
+            HirExpression::Ident(ident) => {
+                let definition_info = self.definition(ident.id);
+                match definition_info.kind {
+                    DefinitionKind::Function(func_id) => {
+                        let typ = self.function_meta(&func_id).typ();
+                        Some(HoverInfo { typ, kind: HoverKind::Function(func_id) })
+                    }
+                    DefinitionKind::Local(_) => {
+                        Some(HoverInfo { typ: self.id_type(ident.id), kind: HoverKind::Local })
+                    }
+                    DefinitionKind::Global(global_id) => {
+                        Some(HoverInfo { typ: self.id_type(ident.id), kind: HoverKind::Global(global_id) })
+                    }
+                    _ => None,
+                }
+            }
+            HirExpression::MemberAccess(expr_member_access) => {
+                let (struct_type, generics) = match self.id_type(&expr_member_access.lhs) {
+                    Type::Struct(struct_type, generics) => (struct_type, generics),
+                    _ => return None,
+                };
+                let struct_type = struct_type.borrow();
+                let (field_type, _) =
+                    struct_type.get_field(&expr_member_access.rhs.0.contents, &generics)?;
+                Some(HoverInfo { typ: field_type, kind: HoverKind::StructField })
+            }
+            HirExpression::Call(expr_call) => {
+                let func_type = self.id_type(expr_call.func);
+                let return_type = match func_type {
+                    Type::Function(_, return_type, _) => *return_type,
+                    other => other,
+                };
+                // The callee resolves to a concrete function when it's a
+                // direct call (`foo()`); for an indirect call through a
+                // function value there's no single `FuncId` to point at.
+                let kind = self
+                    .call_target_func_id(expr_call.func)
+                    .map(HoverKind::Function)
+                    .unwrap_or(HoverKind::Call);
+                Some(HoverInfo { typ: return_type, kind })
+            }
+            _ => None,
+        }
+    }
+
+    /// Renders the `fn name(Type, ..) -> Type` signature for `func_id`, for
+    /// hover tooltips on a [HoverKind::Function]. Only the parameter types
+    /// are rendered; parameter names are not included.
+    pub fn function_signature(&self, func_id: FuncId) -> String {
+        let name = self.function_name(&func_id);
+        match self.function_meta(&func_id).typ() {
+            Type::Function(args, return_type, _env) => {
+                let params = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(", ");
+                format!("fn {name}({params}) -> {return_type}")
+            }
+            other => format!("fn {name}(..) -> {other}"),
+        }
+    }
+
+    /// Resolves a call's callee expression to the [FuncId] it directly
+    /// names, if any (i.e. `foo()` where `foo` is an `Ident` bound to a
+    /// function, as opposed to a function value stored in a variable).
+    fn call_target_func_id(&self, func: impl Into<Index>) -> Option<FuncId> {
+        let Node::Expression(HirExpression::Ident(ident)) = self.nodes.get(func.into())? else {
+            return None;
+        };
+        match self.definition(ident.id).kind {
+            DefinitionKind::Function(func_id) => Some(func_id),
+            _ => None,
+        }
+    }
+
+    /// Returns every [Location] that references the symbol found at `location`,
+    /// optionally including the declaration itself.
+    ///
+    /// The symbol under the cursor is first resolved to a canonical
+    /// [ReferenceId] (reusing [NodeInterner::find_location_index] and
+    /// [NodeInterner::reference_id_for_expression]), then
+    /// every other interned expression is scanned via
+    /// [NodeInterner::references_to] for ones resolving to that same id.
+    /// There's no reverse index populated as expressions are interned (that
+    /// would need a hook wired into wherever `HirExpression::Ident`,
+    /// `MemberAccess` and `Call` nodes get pushed), so this is a linear pass
+    /// per query rather than an O(1) map lookup; it trades that for never
+    /// serving references recorded against a node that's since been edited
+    /// out, which a populate-once-and-never-invalidate cache could.
+    pub fn find_all_references(
+        &self,
+        location: Location,
+        include_declaration: bool,
+    ) -> Vec<Location> {
+        let Some(reference_id) = self.find_reference_id(location) else {
+            return Vec::new();
+        };
+
+        let mut locations = self.references_to(&reference_id);
+
+        if include_declaration {
+            if let Some(declaration) = self.resolve_reference_id_location(&reference_id) {
+                locations.push(declaration);
+            }
+        }
+
+        locations
+    }
+
+    /// Scans every interned expression for ones whose resolved [ReferenceId]
+    /// equals `target`, returning their locations (deduped by span, since
+    /// nothing here guarantees an `Index` is only ever visited once).
+    fn references_to(&self, target: &ReferenceId) -> Vec<Location> {
+        let mut seen = HashSet::new();
+        self.nodes
+            .iter()
+            .filter_map(|(index, node)| {
+                let Node::Expression(expression) = node else { return None };
+                let reference_id = self.reference_id_for_expression(expression)?;
+                if reference_id != *target {
+                    return None;
+                }
+                self.id_to_location.get(&index).copied()
+            })
+            .filter(|location| seen.insert((location.file, location.span.start(), location.span.end())))
+            .collect()
+    }
+
+    /// Resolves the symbol at `location` to the [ReferenceId] it would match
+    /// in [NodeInterner::references_to].
+    fn find_reference_id(&self, location: Location) -> Option<ReferenceId> {
+        let index = self.find_location_index(location)?;
+        let Node::Expression(expression) = self.nodes.get(index.into())? else { return None };
+        self.reference_id_for_expression(expression)
+    }
+
+    /// Resolves a single interned expression to the [ReferenceId] it refers
+    /// to, mirroring the `Ident`, `MemberAccess` and `Call` arms of
+    /// [NodeInterner::resolve_expression_location]. A call's callee resolves
+    /// through its own `Ident`, so `foo()` and `foo` reference the same id.
+    /// Every other expression, method calls included, yields `None`, so this
+    /// never produces a [ReferenceId::TraitMethod].
+    fn reference_id_for_expression(&self, expression: &HirExpression) -> Option<ReferenceId> {
+        match expression {
+            HirExpression::Ident(ident) => Some(ReferenceId::Definition(ident.id)),
+            HirExpression::MemberAccess(expr_member_access) => {
+                let struct_type = match self.id_type(&expr_member_access.lhs) {
+                    Type::Struct(struct_type, _) => struct_type,
+                    _ => return None,
+                };
+                let struct_type = struct_type.borrow();
+                Some(ReferenceId::StructField(struct_type.id, expr_member_access.rhs.0.contents.clone()))
+            }
+            HirExpression::Call(expr_call) => {
+                self.find_reference_id(self.id_location(expr_call.func))
+            }
+            _ => None,
+        }
+    }
+
+    /// Resolves a [ReferenceId] back to the [Location] of its declaration,
+    /// reusing the same lookups as [NodeInterner::resolve_expression_location].
+    fn resolve_reference_id_location(&self, reference_id: &ReferenceId) -> Option<Location> {
+        match reference_id {
+            ReferenceId::Definition(definition_id) => {
+                let definition_info = self.definition(*definition_id);
+                match definition_info.kind {
+                    DefinitionKind::Function(func_id) => {
+                        Some(self.function_meta(&func_id).location)
+                    }
+                    _ => Some(definition_info.location),
+                }
+            }
+            ReferenceId::StructField(struct_id, field_name) => {
+                let struct_type = self.get_struct(*struct_id);
+                let struct_type = struct_type.borrow();
+                struct_type
+                    .field_names()
+                    .iter()
+                    .find(|name| name.0 == *field_name)
+                    .map(|name| Location::new(name.span(), struct_type.location.file))
+            }
+            ReferenceId::TraitMethod(trait_id, name) => self
+                .traits
+                .get(trait_id)?
+                .methods
+                .iter()
+                .find(|method| method.name.0.contents == *name)
+                .map(|method| method.location),
+        }
+    }
+
     /// For a given [Index] we return [Location] to which we resolved to
     /// We currently return None for features not yet implemented
     /// TODO(#3659): LSP goto def should error when Ident at Location could not resolve
@@ -65,7 +401,8 @@ impl NodeInterner {
 
     /// Resolves the [Location] of the definition for a given [HirExpression]
     ///
-    /// Note: current the code returns None because some expressions are not yet implemented.
+    /// Note: still returns `None` for expressions with no well-defined
+    /// "definition" to jump to (literals, operators, etc).
     fn resolve_expression_location(&self, expression: &HirExpression) -> Option<Location> {
         match expression {
             HirExpression::Ident(ident) => {
@@ -80,6 +417,7 @@ impl NodeInterner {
                 }
             }
             HirExpression::Constructor(expr) => {
+                // Jump from `Foo { ... }` to the declaration of `struct Foo`.
                 let struct_type = &expr.r#type.borrow();
                 Some(struct_type.location)
             }
@@ -90,11 +428,68 @@ impl NodeInterner {
                 let func = expr_call.func;
                 self.resolve_location(func)
             }
+            HirExpression::MethodCall(method_call) => {
+                self.resolve_method_call_location(method_call)
+            }
+            HirExpression::Index(index_expr) => self.resolve_location(index_expr.collection),
+            HirExpression::Cast(cast_expr) => self.resolve_location(cast_expr.lhs),
+            HirExpression::Block(block_expr) => block_expr
+                .statements
+                .last()
+                .and_then(|stmt_id| self.resolve_statement_location(*stmt_id)),
+            HirExpression::If(if_expr) => self
+                .resolve_location(if_expr.consequence)
+                .or_else(|| if_expr.alternative.and_then(|alt| self.resolve_location(alt))),
 
             _ => None,
         }
     }
 
+    /// Resolves a method call like `x.foo()` to the [Location] of the
+    /// concrete `fn foo` it dispatches to: an inherent method on the
+    /// receiver's type via the usual method-resolution path
+    /// ([NodeInterner::lookup_method]), falling back to a direct scan of
+    /// [NodeInterner::trait_implementations] for trait methods it doesn't
+    /// cover (the same kind of trait/impl correlation
+    /// [NodeInterner::try_resolve_trait_method_declaration] does from the
+    /// call site back up to the trait).
+    fn resolve_method_call_location(
+        &self,
+        method_call: &crate::hir_def::expr::HirMethodCallExpression,
+    ) -> Option<Location> {
+        let object_type = self.id_type(method_call.object);
+        let method_name = &method_call.method.0.contents;
+
+        if let Some(func_id) = self.lookup_method(&object_type, method_name, false) {
+            return Some(self.function_meta(&func_id).location);
+        }
+
+        self.trait_implementations.iter().find_map(|shared_trait_impl| {
+            let trait_impl = shared_trait_impl.borrow();
+            if trait_impl.typ != object_type {
+                return None;
+            }
+
+            let func_id = trait_impl
+                .methods
+                .iter()
+                .find(|func_id| self.function_name(func_id) == method_name)?;
+            Some(self.function_meta(func_id).location)
+        })
+    }
+
+    /// Recurses into a block's tail statement when it's an expression
+    /// statement, so goto-definition on a block or `if` tail falls through
+    /// to whatever that tail expression resolves to.
+    fn resolve_statement_location(&self, stmt_id: crate::hir_def::stmt::StmtId) -> Option<Location> {
+        match self.statement(&stmt_id) {
+            crate::hir_def::stmt::HirStatement::Expression(expr_id) => {
+                self.resolve_location(expr_id)
+            }
+            _ => None,
+        }
+    }
+
     /// Resolves the [Location] of the definition for a given [crate::hir_def::expr::HirMemberAccess]
     /// This is used to resolve the location of a struct member access.
     /// For example, in the expression `foo.bar` we want to resolve the location of `bar`
@@ -137,6 +532,148 @@ impl NodeInterner {
             })
     }
 
+    /// Given a [Location] on the trait's name in an `impl Trait for Type`
+    /// header, returns the trait methods that are declared on the trait but
+    /// missing from the impl, as `(name, stub source)` pairs ready to be
+    /// inserted by an LSP "Implement missing trait members" code action. A
+    /// location elsewhere in the impl block matches no impl and yields an
+    /// empty list.
+    ///
+    /// This reuses the same trait/impl correlation as
+    /// [NodeInterner::try_resolve_trait_impl_location] and the same method
+    /// metadata lookup as [NodeInterner::try_resolve_trait_method_declaration],
+    /// rather than re-parsing the impl.
+    pub fn missing_trait_impl_members(&self, impl_location: Location) -> Vec<(String, String)> {
+        let Some(shared_trait_impl) = self.trait_implementations.iter().find(|shared_trait_impl| {
+            let trait_impl = shared_trait_impl.borrow();
+            trait_impl.file == impl_location.file
+                && trait_impl.ident.span().contains(&impl_location.span)
+        }) else {
+            return Vec::new();
+        };
+
+        let trait_impl = shared_trait_impl.borrow();
+        let Some(trait_) = self.traits.get(&trait_impl.trait_id) else { return Vec::new() };
+
+        let implemented: HashSet<&str> = trait_impl
+            .methods
+            .iter()
+            .map(|func_id| self.function_name(func_id))
+            .collect();
+
+        let substitution = Self::trait_generic_substitution(trait_, &trait_impl.trait_generics);
+
+        trait_
+            .methods
+            .iter()
+            .filter(|method| !implemented.contains(method.name.0.contents.as_str()))
+            .map(|method| {
+                let name = method.name.0.contents.clone();
+                let stub = Self::missing_trait_method_stub(method, &substitution);
+                (name, stub)
+            })
+            .collect()
+    }
+
+    /// Maps each of the trait's generic parameters, by name, to the concrete
+    /// type given for it at the impl site (`impl Foo<Field> for Bar` pairs
+    /// the trait's first generic with `Field`, and so on).
+    fn trait_generic_substitution(
+        trait_: &crate::hir_def::traits::Trait,
+        impl_generics: &[Type],
+    ) -> HashMap<String, Type> {
+        trait_
+            .generics
+            .iter()
+            .map(|generic| generic.name.to_string())
+            .zip(impl_generics.iter().cloned())
+            .collect()
+    }
+
+    /// Renders the insertable stub for a trait method missing from an impl:
+    /// the method's signature, with the trait's generics substituted for the
+    /// impl's concrete type arguments, and a `todo` body.
+    fn missing_trait_method_stub(
+        method: &crate::hir_def::traits::TraitFunction,
+        substitution: &HashMap<String, Type>,
+    ) -> String {
+        let name = &method.name.0.contents;
+        let parameters = method
+            .arguments
+            .iter()
+            .enumerate()
+            .map(|(i, typ)| {
+                let typ = Self::substitute_trait_generics(typ, substitution);
+                if i == 0 {
+                    if let Some(receiver) = Self::format_self_receiver(&typ.to_string()) {
+                        return receiver;
+                    }
+                }
+                format!("_{i}: {typ}")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let return_type = Self::substitute_trait_generics(&method.return_type, substitution);
+
+        format!("fn {name}({parameters}) -> {return_type} {{\n    todo()\n}}")
+    }
+
+    /// Renders a parameter's already-formatted type as a bare
+    /// `self`/`&self`/`&mut self` receiver if it's the trait's own `Self`
+    /// type (or a reference to it), so a method that takes a receiver gets
+    /// one in its generated stub instead of a synthesized `_0: Self`
+    /// parameter, which isn't valid receiver syntax and wouldn't match the
+    /// trait's signature. Takes the rendered type rather than a [Type] so
+    /// the detection logic can be unit tested without constructing one.
+    fn format_self_receiver(rendered_type: &str) -> Option<String> {
+        match rendered_type {
+            "Self" => Some("self".to_string()),
+            "&Self" => Some("&self".to_string()),
+            "&mut Self" => Some("&mut self".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Substitutes a trait's generic parameters with the concrete type
+    /// arguments used at an impl site, by the generic's actual name rather
+    /// than its position, recursing into compound types so a generic nested
+    /// inside e.g. an array, tuple, or another struct's type arguments is
+    /// substituted too. Types that aren't one of the trait's generics
+    /// (concrete types, `Self`) are left untouched.
+    fn substitute_trait_generics(typ: &Type, substitution: &HashMap<String, Type>) -> Type {
+        match typ {
+            Type::NamedGeneric(_, name) => {
+                substitution.get(name.as_ref()).cloned().unwrap_or_else(|| typ.clone())
+            }
+            Type::Array(len, element) => Type::Array(
+                len.clone(),
+                Box::new(Self::substitute_trait_generics(element, substitution)),
+            ),
+            Type::Slice(element) => {
+                Type::Slice(Box::new(Self::substitute_trait_generics(element, substitution)))
+            }
+            Type::Tuple(elements) => Type::Tuple(
+                elements.iter().map(|elem| Self::substitute_trait_generics(elem, substitution)).collect(),
+            ),
+            Type::Struct(struct_type, generics) => Type::Struct(
+                struct_type.clone(),
+                generics
+                    .iter()
+                    .map(|generic| Self::substitute_trait_generics(generic, substitution))
+                    .collect(),
+            ),
+            Type::MutableReference(element) => Type::MutableReference(Box::new(
+                Self::substitute_trait_generics(element, substitution),
+            )),
+            Type::Function(args, ret, env) => Type::Function(
+                args.iter().map(|arg| Self::substitute_trait_generics(arg, substitution)).collect(),
+                Box::new(Self::substitute_trait_generics(ret, substitution)),
+                Box::new(Self::substitute_trait_generics(env, substitution)),
+            ),
+            other => other.clone(),
+        }
+    }
+
     /// Attempts to resolve [Location] of [Trait]'s [TraitFunction] declaration based on [Location] of [TraitFunction] call.
     ///
     /// This is used by LSP to resolve the location.
@@ -168,3 +705,76 @@ impl NodeInterner {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FileIntervalIndex, NodeInterner};
+    use noirc_errors::Span;
+
+    // `FileIntervalIndex` and `format_self_receiver` are the pieces of this
+    // file's additions that are self-contained enough to unit test without a
+    // full `NodeInterner`. Exercising the `MethodCall`/`Index`/`Cast`/
+    // `Block`/`If` arms of `resolve_expression_location`, `find_all_references`
+    // end to end, or hover needs a `NodeInterner` populated with real
+    // `HirExpression`/`Type` values from compiling source, which these unit
+    // tests don't do - those belong in the `tooling/lsp` crate's
+    // fixture-based tests instead.
+
+    #[test]
+    fn formats_self_receivers() {
+        assert_eq!(NodeInterner::format_self_receiver("Self"), Some("self".to_string()));
+        assert_eq!(NodeInterner::format_self_receiver("&Self"), Some("&self".to_string()));
+        assert_eq!(NodeInterner::format_self_receiver("&mut Self"), Some("&mut self".to_string()));
+        assert_eq!(NodeInterner::format_self_receiver("Field"), None);
+        assert_eq!(NodeInterner::format_self_receiver("SelfType"), None);
+    }
+
+    #[test]
+    fn finds_containing_interval() {
+        let mut index = FileIntervalIndex::default();
+        index.insert(Span::from(0..10), "outer");
+        index.insert(Span::from(2..5), "inner");
+
+        assert_eq!(index.find_smallest_containing(Span::from(3..4)), Some("inner"));
+    }
+
+    #[test]
+    fn prefers_the_smallest_containing_interval() {
+        let mut index = FileIntervalIndex::default();
+        index.insert(Span::from(0..20), "outermost");
+        index.insert(Span::from(2..10), "middle");
+        index.insert(Span::from(4..6), "innermost");
+
+        assert_eq!(index.find_smallest_containing(Span::from(4..5)), Some("innermost"));
+    }
+
+    #[test]
+    fn requires_the_full_query_span_to_be_contained() {
+        let mut index = FileIntervalIndex::default();
+        // Starts before the query but ends before it too: must not match,
+        // since it doesn't contain the query's end.
+        index.insert(Span::from(0..3), "too_short");
+
+        assert_eq!(index.find_smallest_containing(Span::from(1..5)), None);
+    }
+
+    #[test]
+    fn returns_none_when_nothing_contains_the_query() {
+        let mut index = FileIntervalIndex::default();
+        index.insert(Span::from(10..20), "unrelated");
+
+        assert_eq!(index.find_smallest_containing(Span::from(0..5)), None);
+    }
+
+    #[test]
+    fn insert_after_query_keeps_the_index_correct() {
+        let mut index = FileIntervalIndex::default();
+        index.insert(Span::from(0..10), "first");
+        assert_eq!(index.find_smallest_containing(Span::from(1..2)), Some("first"));
+
+        // Inserting a smaller, later-discovered interval should win on the
+        // next query instead of the stale result being cached.
+        index.insert(Span::from(1..3), "second");
+        assert_eq!(index.find_smallest_containing(Span::from(1..2)), Some("second"));
+    }
+}